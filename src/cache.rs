@@ -2,7 +2,11 @@ use std::convert::TryInto;
 use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::str::from_utf8;
 
-use cosmwasm_vm::{features_from_csv, Cache, CacheOptions, Checksum, Size};
+use cosmwasm_vm::{
+    features_from_csv, Cache, CacheOptions, Checksum, CompilerBackend, CostTable,
+    GasMeteringConfig, Size,
+};
+use serde::{Deserialize, Serialize};
 
 use crate::api::GoApi;
 use crate::args::{CACHE_ARG, DATA_DIR_ARG, FEATURES_ARG, WASM_ARG};
@@ -23,12 +27,90 @@ pub fn to_cache(ptr: *mut cache_t) -> Option<&'static mut Cache<GoApi, GoStorage
     }
 }
 
+/// Cache hit/miss counters tracked by the cache layer, reported to the Go side
+/// so node operators can tune `cache_size` against real workloads.
+///
+/// `hits_pinned` counts lookups served from the pinned set (outside the LRU budget),
+/// `hits_instance` counts lookups served from the in-memory instance/module LRU,
+/// `hits_module` counts modules loaded from the on-disk module cache after missing
+/// the in-memory LRU, and `misses` counts modules that had to be compiled from raw Wasm.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+struct CacheMetrics {
+    hits_pinned: u32,
+    hits_instance: u32,
+    hits_module: u32,
+    misses: u32,
+}
+
+/// Maps the cache's internal stats counters onto the wire format. Kept free of
+/// the `GoApi`/`GoStorage`/`GoQuerier` type parameters (unlike `cache_t`) so it
+/// can be exercised in tests against a `Cache` driven through its real
+/// get/instantiate path, which is the only thing that actually moves these
+/// counters -- `GoApi` et al. can only be constructed from the Go side and
+/// can't be driven from a Rust-only test.
+fn metrics_from_stats(stats: cosmwasm_vm::Stats) -> CacheMetrics {
+    CacheMetrics {
+        hits_pinned: stats.hits_pinned_memory_cache,
+        hits_instance: stats.hits_memory_cache,
+        hits_module: stats.hits_fs_cache,
+        misses: stats.misses,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn get_cache_metrics(cache: *mut cache_t, err: Option<&mut Buffer>) -> Buffer {
+    let r = match to_cache(cache) {
+        Some(c) => catch_unwind(AssertUnwindSafe(move || do_get_cache_metrics(c)))
+            .unwrap_or_else(|_| Err(Error::panic())),
+        None => Err(Error::empty_arg(CACHE_ARG)),
+    };
+    let data = handle_c_error(r, err);
+    Buffer::from_vec(data)
+}
+
+fn do_get_cache_metrics(cache: &mut Cache<GoApi, GoStorage, GoQuerier>) -> Result<Vec<u8>, Error> {
+    let metrics = metrics_from_stats(cache.stats());
+    serde_json::to_vec(&metrics).map_err(|e| Error::generic_err(e.to_string()))
+}
+
+/// Parses the `compiler_backend` buffer into a `CompilerBackend`. An empty buffer
+/// (including a null one) keeps the current default of `Singlepass`, so existing
+/// callers that don't pass this argument see identical behavior to before.
+fn parse_compiler_backend(raw: &[u8]) -> Result<CompilerBackend, Error> {
+    match raw {
+        b"" | b"singlepass" => Ok(CompilerBackend::Singlepass),
+        b"cranelift" => Ok(CompilerBackend::Cranelift),
+        other => Err(Error::generic_err(format!(
+            "unknown compiler backend: {}",
+            String::from_utf8_lossy(other)
+        ))),
+    }
+}
+
+/// Parses the `gas_metering_cost_table` buffer into an optional `GasMeteringConfig`.
+/// An empty buffer disables the save-time validation+instrumentation pass entirely,
+/// matching the current default behavior. A non-empty buffer is the JSON-encoded
+/// `CostTable` to use; pass `{}` to enable instrumentation with the default costs.
+fn parse_gas_metering_config(raw: &[u8]) -> Result<Option<GasMeteringConfig>, Error> {
+    if raw.is_empty() {
+        return Ok(None);
+    }
+    let cost_table: CostTable =
+        serde_json::from_slice(raw).map_err(|e| Error::generic_err(e.to_string()))?;
+    Ok(Some(GasMeteringConfig {
+        enabled: true,
+        cost_table,
+    }))
+}
+
 #[no_mangle]
 pub extern "C" fn init_cache(
     data_dir: Buffer,
     supported_features: Buffer,
     cache_size: u32,
     instance_memory_limit: u32,
+    compiler_backend: Buffer,
+    gas_metering_cost_table: Buffer,
     err: Option<&mut Buffer>,
 ) -> *mut cache_t {
     let r = catch_unwind(|| {
@@ -37,6 +119,8 @@ pub extern "C" fn init_cache(
             supported_features,
             cache_size,
             instance_memory_limit,
+            compiler_backend,
+            gas_metering_cost_table,
         )
     })
     .unwrap_or_else(|_| Err(Error::panic()));
@@ -57,6 +141,8 @@ fn do_init_cache(
     supported_features: Buffer,
     cache_size: u32,
     instance_memory_limit: u32, // in MiB
+    compiler_backend: Buffer,
+    gas_metering_cost_table: Buffer,
 ) -> Result<*mut Cache<GoApi, GoStorage, GoQuerier>, Error> {
     let dir = unsafe { data_dir.read() }.ok_or_else(|| Error::empty_arg(DATA_DIR_ARG))?;
     let dir_str = String::from_utf8(dir.to_vec())?;
@@ -75,11 +161,19 @@ fn do_init_cache(
             .try_into()
             .expect("Cannot convert u32 to usize. What kind of system is this?"),
     );
+    // an empty buffer means "use the default", so a null pointer is fine here too
+    let compiler_backend_bin = unsafe { compiler_backend.read() }.unwrap_or(&[]);
+    let compiler_backend = parse_compiler_backend(compiler_backend_bin)?;
+    // an empty buffer means "gas metering disabled", so a null pointer is fine here too
+    let gas_metering_bin = unsafe { gas_metering_cost_table.read() }.unwrap_or(&[]);
+    let gas_metering = parse_gas_metering_config(gas_metering_bin)?;
     let options = CacheOptions {
         base_dir: dir_str.into(),
         supported_features: features,
         memory_cache_size,
         instance_memory_limit,
+        compiler_backend,
+        gas_metering,
     };
     let cache = unsafe { Cache::new(options) }?;
     let out = Box::new(cache);
@@ -132,6 +226,192 @@ fn do_load_wasm(
     Ok(wasm)
 }
 
+#[no_mangle]
+pub extern "C" fn pin_wasm(
+    cache: *mut cache_t,
+    contract_checksum: Buffer,
+    err: Option<&mut Buffer>,
+) {
+    let r = match to_cache(cache) {
+        Some(c) => catch_unwind(AssertUnwindSafe(move || do_pin_wasm(c, contract_checksum)))
+            .unwrap_or_else(|_| Err(Error::panic())),
+        None => Err(Error::empty_arg(CACHE_ARG)),
+    };
+    if let Err(e) = r {
+        set_error(e, err);
+    } else {
+        clear_error();
+    }
+}
+
+fn do_pin_wasm(
+    cache: &mut Cache<GoApi, GoStorage, GoQuerier>,
+    contract_checksum: Buffer,
+) -> Result<(), Error> {
+    let contract_checksum: Checksum = unsafe { contract_checksum.read() }
+        .ok_or_else(|| Error::empty_arg(CACHE_ARG))?
+        .try_into()?;
+    cache.pin(&contract_checksum)?;
+    Ok(())
+}
+
+/// Size in bytes of a `Checksum`, used to split the checksum header off an
+/// exported artifact.
+const CHECKSUM_LENGTH: usize = 32;
+
+/// Size in bytes of the two checksums prefixed onto an exported artifact: the
+/// export-time contract checksum, followed by a hash of the artifact bytes
+/// themselves.
+const ARTIFACT_HEADER_LENGTH: usize = 2 * CHECKSUM_LENGTH;
+
+#[no_mangle]
+pub extern "C" fn export_compiled(
+    cache: *mut cache_t,
+    contract_checksum: Buffer,
+    err: Option<&mut Buffer>,
+) -> Buffer {
+    let r = match to_cache(cache) {
+        Some(c) => catch_unwind(AssertUnwindSafe(move || do_export_compiled(c, contract_checksum)))
+            .unwrap_or_else(|_| Err(Error::panic())),
+        None => Err(Error::empty_arg(CACHE_ARG)),
+    };
+    let data = handle_c_error(r, err);
+    Buffer::from_vec(data)
+}
+
+fn do_export_compiled(
+    cache: &mut Cache<GoApi, GoStorage, GoQuerier>,
+    contract_checksum: Buffer,
+) -> Result<Vec<u8>, Error> {
+    let contract_checksum: Checksum = unsafe { contract_checksum.read() }
+        .ok_or_else(|| Error::empty_arg(CACHE_ARG))?
+        .try_into()?;
+    // compiles the module first if it isn't already sitting in the on-disk module store
+    let artifact = cache.serialized_module(&contract_checksum)?;
+    Ok(pack_artifact(&contract_checksum, &artifact))
+}
+
+/// Prefixes a serialized module with two checksums: the contract checksum
+/// trusted at export time, so `unpack_artifact` can bind the artifact to the
+/// checksum the caller supplies there instead of just trusting it; and a hash
+/// of the artifact bytes themselves, so corruption or substitution in transit
+/// is also caught even when the supplied checksum happens to match.
+fn pack_artifact(contract_checksum: &Checksum, artifact: &[u8]) -> Vec<u8> {
+    let artifact_checksum = Checksum::generate(artifact);
+    let mut out = Vec::with_capacity(ARTIFACT_HEADER_LENGTH + artifact.len());
+    out.extend_from_slice(contract_checksum.as_slice());
+    out.extend_from_slice(artifact_checksum.as_slice());
+    out.extend_from_slice(artifact);
+    out
+}
+
+/// Inverse of `pack_artifact`: verifies both embedded checksums against
+/// `contract_checksum` and the artifact's own content, then returns the
+/// module bytes ready to hand to `Cache::import_serialized_module`.
+fn unpack_artifact<'a>(
+    contract_checksum: &Checksum,
+    artifact: &'a [u8],
+) -> Result<&'a [u8], Error> {
+    if artifact.len() < ARTIFACT_HEADER_LENGTH {
+        return Err(Error::generic_err(
+            "artifact too short to contain its embedded checksums",
+        ));
+    }
+    let (header, module) = artifact.split_at(ARTIFACT_HEADER_LENGTH);
+    let (embedded_contract_checksum, embedded_artifact_checksum) =
+        header.split_at(CHECKSUM_LENGTH);
+    let embedded_contract_checksum: Checksum = embedded_contract_checksum.try_into()?;
+    let embedded_artifact_checksum: Checksum = embedded_artifact_checksum.try_into()?;
+
+    // bind the artifact to the checksum the caller actually asked to import,
+    // rather than trusting `contract_checksum` on its own: this is what stops a
+    // buggy or malicious import from registering one contract's compiled
+    // module under another contract's checksum in the module store.
+    if embedded_contract_checksum != *contract_checksum {
+        return Err(Error::generic_err(format!(
+            "artifact checksum {:?} does not match supplied checksum {:?}",
+            embedded_contract_checksum, contract_checksum
+        )));
+    }
+
+    // re-derive the checksum from the module bytes we actually received, rather
+    // than trusting the embedded header on its own: this is what catches a
+    // payload corrupted or substituted on the wire before it reaches the
+    // engine's native module deserializer.
+    let actual_artifact_checksum = Checksum::generate(module);
+    if actual_artifact_checksum != embedded_artifact_checksum {
+        return Err(Error::generic_err(
+            "artifact content does not match its embedded checksum; it may have been corrupted or substituted in transit",
+        ));
+    }
+
+    Ok(module)
+}
+
+#[no_mangle]
+pub extern "C" fn import_compiled(
+    cache: *mut cache_t,
+    contract_checksum: Buffer,
+    artifact: Buffer,
+    err: Option<&mut Buffer>,
+) {
+    let r = match to_cache(cache) {
+        Some(c) => catch_unwind(AssertUnwindSafe(move || {
+            do_import_compiled(c, contract_checksum, artifact)
+        }))
+        .unwrap_or_else(|_| Err(Error::panic())),
+        None => Err(Error::empty_arg(CACHE_ARG)),
+    };
+    if let Err(e) = r {
+        set_error(e, err);
+    } else {
+        clear_error();
+    }
+}
+
+fn do_import_compiled(
+    cache: &mut Cache<GoApi, GoStorage, GoQuerier>,
+    contract_checksum: Buffer,
+    artifact: Buffer,
+) -> Result<(), Error> {
+    let contract_checksum: Checksum = unsafe { contract_checksum.read() }
+        .ok_or_else(|| Error::empty_arg(CACHE_ARG))?
+        .try_into()?;
+    let artifact = unsafe { artifact.read() }.ok_or_else(|| Error::empty_arg(WASM_ARG))?;
+    let module = unpack_artifact(&contract_checksum, artifact)?;
+    cache.import_serialized_module(&contract_checksum, module)?;
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn unpin_wasm(
+    cache: *mut cache_t,
+    contract_checksum: Buffer,
+    err: Option<&mut Buffer>,
+) {
+    let r = match to_cache(cache) {
+        Some(c) => catch_unwind(AssertUnwindSafe(move || do_unpin_wasm(c, contract_checksum)))
+            .unwrap_or_else(|_| Err(Error::panic())),
+        None => Err(Error::empty_arg(CACHE_ARG)),
+    };
+    if let Err(e) = r {
+        set_error(e, err);
+    } else {
+        clear_error();
+    }
+}
+
+fn do_unpin_wasm(
+    cache: &mut Cache<GoApi, GoStorage, GoQuerier>,
+    contract_checksum: Buffer,
+) -> Result<(), Error> {
+    let contract_checksum: Checksum = unsafe { contract_checksum.read() }
+        .ok_or_else(|| Error::empty_arg(CACHE_ARG))?
+        .try_into()?;
+    cache.unpin(&contract_checksum)?;
+    Ok(())
+}
+
 /// frees a cache reference
 ///
 /// # Safety
@@ -149,10 +429,33 @@ pub extern "C" fn release_cache(cache: *mut cache_t) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use cosmwasm_vm::testing::{
+        mock_backend, mock_env, mock_info, mock_instance_options, MockApi, MockQuerier, MockStorage,
+    };
     use tempfile::TempDir;
 
     static CONTRACT: &[u8] = include_bytes!("../api/testdata/hackatom.wasm");
 
+    /// Builds a cache backed by `cosmwasm_vm`'s own mock API/storage/querier rather
+    /// than `GoApi`/`GoStorage`/`GoQuerier`, which can only be constructed from the
+    /// Go side. Used by tests that need to drive the real get/instantiate path --
+    /// the only thing that actually moves the cache's hit/miss counters -- since
+    /// `load_wasm` is just a raw fetch of the stored bytes and never touches them.
+    fn mock_cache(options: CacheOptions) -> Cache<MockApi, MockStorage, MockQuerier> {
+        unsafe { Cache::new(options) }.unwrap()
+    }
+
+    fn mock_cache_options(dir: String) -> CacheOptions {
+        CacheOptions {
+            base_dir: dir.into(),
+            supported_features: features_from_csv("staking"),
+            memory_cache_size: Size::mebi(512),
+            instance_memory_limit: Size::mebi(32),
+            compiler_backend: CompilerBackend::Singlepass,
+            gas_metering: None,
+        }
+    }
+
     #[test]
     fn init_cache_and_release_cache_work() {
         let dir: String = TempDir::new().unwrap().path().to_str().unwrap().to_owned();
@@ -163,6 +466,8 @@ mod tests {
             features.into(),
             512,
             32,
+            b"".into(),
+            b"".into(),
             Some(&mut err),
         );
         assert_eq!(err.len, 0);
@@ -179,6 +484,8 @@ mod tests {
             features.into(),
             512,
             32,
+            b"".into(),
+            b"".into(),
             Some(&mut err),
         );
         assert!(cache_ptr.is_null());
@@ -197,6 +504,8 @@ mod tests {
             features.into(),
             512,
             32,
+            b"".into(),
+            b"".into(),
             Some(&mut err),
         );
         assert_eq!(err.len, 0);
@@ -217,6 +526,8 @@ mod tests {
             features.into(),
             512,
             32,
+            b"".into(),
+            b"".into(),
             Some(&mut err),
         );
         assert_eq!(err.len, 0);
@@ -229,4 +540,388 @@ mod tests {
 
         release_cache(cache_ptr);
     }
+
+    #[test]
+    fn get_cache_metrics_is_well_formed_before_any_instantiate() {
+        // save_wasm only stores the bytes; it never compiles or instantiates the
+        // module, so the counters must still read zero afterwards
+        let dir: String = TempDir::new().unwrap().path().to_str().unwrap().to_owned();
+        let mut err = Buffer::default();
+        let features: &[u8] = b"staking";
+        let cache_ptr = init_cache(
+            dir.as_bytes().into(),
+            features.into(),
+            512,
+            32,
+            b"".into(),
+            b"".into(),
+            Some(&mut err),
+        );
+        assert_eq!(err.len, 0);
+
+        save_wasm(cache_ptr, CONTRACT.into(), Some(&mut err));
+        assert_eq!(err.len, 0);
+
+        let metrics_data = get_cache_metrics(cache_ptr, Some(&mut err));
+        assert_eq!(err.len, 0);
+        let metrics: CacheMetrics = serde_json::from_slice(&unsafe { metrics_data.consume() }).unwrap();
+        assert_eq!(
+            metrics,
+            CacheMetrics {
+                hits_pinned: 0,
+                hits_instance: 0,
+                hits_module: 0,
+                misses: 0,
+            }
+        );
+
+        release_cache(cache_ptr);
+    }
+
+    #[test]
+    fn metrics_from_stats_tracks_compile_and_instantiate_hits_and_misses() {
+        let dir: String = TempDir::new().unwrap().path().to_str().unwrap().to_owned();
+        let mut cache = mock_cache(mock_cache_options(dir));
+
+        let checksum = cache.save_wasm(CONTRACT).unwrap();
+
+        // the first instantiate has to recompile from raw Wasm: a miss
+        cache
+            .get_instance(&checksum, mock_backend(&[]), mock_instance_options())
+            .unwrap();
+        assert_eq!(metrics_from_stats(cache.stats()).misses, 1);
+
+        // the second instantiate is served from the in-memory instance/module LRU: a hit
+        cache
+            .get_instance(&checksum, mock_backend(&[]), mock_instance_options())
+            .unwrap();
+        let metrics = metrics_from_stats(cache.stats());
+        assert_eq!(metrics.hits_instance, 1);
+        assert_eq!(metrics.misses, 1);
+    }
+
+    #[test]
+    fn pin_wasm_and_get_instance_does_not_miss() {
+        // load_wasm is a raw store fetch and never touches the hit/miss counters, so
+        // this drives the cache directly and checks the counters it actually updates
+        let dir: String = TempDir::new().unwrap().path().to_str().unwrap().to_owned();
+        let mut cache = mock_cache(mock_cache_options(dir));
+
+        let checksum = cache.save_wasm(CONTRACT).unwrap();
+        // pinning compiles and warms the pinned cache up front: a miss
+        cache.pin(&checksum).unwrap();
+        assert_eq!(metrics_from_stats(cache.stats()).misses, 1);
+
+        // every instantiate afterwards is served straight out of the pinned cache
+        cache
+            .get_instance(&checksum, mock_backend(&[]), mock_instance_options())
+            .unwrap();
+        cache
+            .get_instance(&checksum, mock_backend(&[]), mock_instance_options())
+            .unwrap();
+        let metrics = metrics_from_stats(cache.stats());
+        assert_eq!(metrics.hits_pinned, 2);
+        assert_eq!(metrics.misses, 1);
+    }
+
+    #[test]
+    fn unpin_wasm_makes_it_evictable_again() {
+        let dir: String = TempDir::new().unwrap().path().to_str().unwrap().to_owned();
+        let mut options = mock_cache_options(dir);
+        // small enough that the module LRU can only hold a handful of filler
+        // modules, so flooding it with a few is enough to evict anything unpinned
+        options.memory_cache_size = Size::kibi(10);
+        let mut cache = mock_cache(options);
+
+        let checksum = cache.save_wasm(CONTRACT).unwrap();
+        cache.pin(&checksum).unwrap();
+        cache
+            .get_instance(&checksum, mock_backend(&[]), mock_instance_options())
+            .unwrap();
+        cache.unpin(&checksum).unwrap();
+
+        // flood the module LRU with distinct filler modules so the now-unpinned
+        // target is no longer recently used and gets evicted from it
+        for i in 0..10 {
+            let filler_wat = format!(
+                r#"(module (func (export "filler") (result i32) i32.const {}))"#,
+                i
+            );
+            let filler_wasm = wat::parse_str(filler_wat).unwrap();
+            let filler_checksum = cache.save_wasm(&filler_wasm).unwrap();
+            cache
+                .get_instance(&filler_checksum, mock_backend(&[]), mock_instance_options())
+                .unwrap();
+        }
+
+        let misses_before = metrics_from_stats(cache.stats()).misses;
+        // the target was evicted once unpinned, so this has to recompile: a fresh miss
+        cache
+            .get_instance(&checksum, mock_backend(&[]), mock_instance_options())
+            .unwrap();
+        assert_eq!(metrics_from_stats(cache.stats()).misses, misses_before + 1);
+    }
+
+    #[test]
+    fn singlepass_and_cranelift_produce_identical_checksums_but_different_artifacts() {
+        let features: &[u8] = b"staking";
+
+        let dir: String = TempDir::new().unwrap().path().to_str().unwrap().to_owned();
+        let mut err = Buffer::default();
+        let cache_ptr = init_cache(
+            dir.as_bytes().into(),
+            features.into(),
+            512,
+            32,
+            b"singlepass".into(),
+            b"".into(),
+            Some(&mut err),
+        );
+        assert_eq!(err.len, 0);
+        let singlepass_checksum = save_wasm(cache_ptr, CONTRACT.into(), Some(&mut err));
+        assert_eq!(err.len, 0);
+        let singlepass_checksum_bytes = unsafe { singlepass_checksum.read() }.unwrap().to_vec();
+        let singlepass_artifact = export_compiled(cache_ptr, singlepass_checksum, Some(&mut err));
+        assert_eq!(err.len, 0);
+        let singlepass_artifact_bytes = unsafe { singlepass_artifact.consume() };
+        release_cache(cache_ptr);
+
+        let dir: String = TempDir::new().unwrap().path().to_str().unwrap().to_owned();
+        let cache_ptr = init_cache(
+            dir.as_bytes().into(),
+            features.into(),
+            512,
+            32,
+            b"cranelift".into(),
+            b"".into(),
+            Some(&mut err),
+        );
+        assert_eq!(err.len, 0);
+        let cranelift_checksum = save_wasm(cache_ptr, CONTRACT.into(), Some(&mut err));
+        assert_eq!(err.len, 0);
+        let cranelift_checksum_bytes = unsafe { cranelift_checksum.read() }.unwrap().to_vec();
+        let cranelift_artifact = export_compiled(cache_ptr, cranelift_checksum, Some(&mut err));
+        assert_eq!(err.len, 0);
+        let cranelift_artifact_bytes = unsafe { cranelift_artifact.consume() };
+        release_cache(cache_ptr);
+
+        // the checksum is a hash of the original Wasm source, so it must be
+        // identical regardless of which compiler backend produced the compiled
+        // module
+        assert_eq!(singlepass_checksum_bytes, cranelift_checksum_bytes);
+
+        // but the exported artifacts -- the actual compiled modules the engines
+        // produced -- must differ. This is what `singlepass_checksum_bytes ==
+        // cranelift_checksum_bytes` alone can't catch: that assertion would also
+        // pass if `compiler_backend` were silently dropped and both caches had
+        // compiled singlepass all along
+        assert_ne!(singlepass_artifact_bytes, cranelift_artifact_bytes);
+    }
+
+    #[test]
+    fn init_cache_rejects_unknown_compiler_backend() {
+        let dir: String = TempDir::new().unwrap().path().to_str().unwrap().to_owned();
+        let mut err = Buffer::default();
+        let features: &[u8] = b"staking";
+        let cache_ptr = init_cache(
+            dir.as_bytes().into(),
+            features.into(),
+            512,
+            32,
+            b"wonkyjit".into(),
+            b"".into(),
+            Some(&mut err),
+        );
+        assert!(cache_ptr.is_null());
+        assert_ne!(err.len, 0);
+    }
+
+    #[test]
+    fn export_compiled_and_import_compiled_roundtrip() {
+        let features: &[u8] = b"staking";
+        let mut err = Buffer::default();
+
+        // compile and export on one "validator", through the production FFI surface
+        let dir: String = TempDir::new().unwrap().path().to_str().unwrap().to_owned();
+        let cache_ptr = init_cache(
+            dir.as_bytes().into(),
+            features.into(),
+            512,
+            32,
+            b"".into(),
+            b"".into(),
+            Some(&mut err),
+        );
+        assert_eq!(err.len, 0);
+        let checksum = save_wasm(cache_ptr, CONTRACT.into(), Some(&mut err));
+        assert_eq!(err.len, 0);
+        let checksum_struct: Checksum = unsafe { checksum.read() }.unwrap().try_into().unwrap();
+        let artifact = export_compiled(cache_ptr, checksum, Some(&mut err));
+        assert_eq!(err.len, 0);
+        let artifact_bytes = unsafe { artifact.consume() };
+        release_cache(cache_ptr);
+
+        // import into a bare mock-backed cache -- a stand-in for a peer that
+        // never compiled this contract -- driving the cache directly rather than
+        // through the FFI surface, since that surface has no instantiate entry
+        // point to read hit/miss counters off of (GoApi/GoStorage/GoQuerier can
+        // only be constructed from the Go side)
+        let dir: String = TempDir::new().unwrap().path().to_str().unwrap().to_owned();
+        let mut cache = mock_cache(mock_cache_options(dir));
+        let module = unpack_artifact(&checksum_struct, &artifact_bytes).unwrap();
+        cache.import_serialized_module(&checksum_struct, module).unwrap();
+
+        // the imported module serves the instantiate straight away -- no
+        // recompile, i.e. no miss -- which is the entire point of importing
+        // a precompiled artifact instead of calling save_wasm
+        cache
+            .get_instance(&checksum_struct, mock_backend(&[]), mock_instance_options())
+            .unwrap();
+        assert_eq!(metrics_from_stats(cache.stats()).misses, 0);
+    }
+
+    #[test]
+    fn import_compiled_rejects_corrupted_artifact() {
+        let dir: String = TempDir::new().unwrap().path().to_str().unwrap().to_owned();
+        let mut err = Buffer::default();
+        let features: &[u8] = b"staking";
+        let cache_ptr = init_cache(
+            dir.as_bytes().into(),
+            features.into(),
+            512,
+            32,
+            b"".into(),
+            b"".into(),
+            Some(&mut err),
+        );
+        assert_eq!(err.len, 0);
+
+        let checksum = save_wasm(cache_ptr, CONTRACT.into(), Some(&mut err));
+        assert_eq!(err.len, 0);
+        let artifact = export_compiled(cache_ptr, checksum, Some(&mut err));
+        assert_eq!(err.len, 0);
+
+        // invert the last byte of the module payload, past the checksum header, as a
+        // stand-in for corruption or substitution in transit
+        let mut corrupted = unsafe { artifact.consume() };
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0xff;
+
+        let checksum = save_wasm(cache_ptr, CONTRACT.into(), Some(&mut err));
+        assert_eq!(err.len, 0);
+        import_compiled(
+            cache_ptr,
+            checksum,
+            corrupted.as_slice().into(),
+            Some(&mut err),
+        );
+        assert_ne!(err.len, 0);
+
+        release_cache(cache_ptr);
+    }
+
+    #[test]
+    fn save_wasm_with_gas_metering_rejects_float_using_contract() {
+        let dir: String = TempDir::new().unwrap().path().to_str().unwrap().to_owned();
+        let mut err = Buffer::default();
+        let features: &[u8] = b"staking";
+        let cache_ptr = init_cache(
+            dir.as_bytes().into(),
+            features.into(),
+            512,
+            32,
+            b"".into(),
+            b"{}".into(),
+            Some(&mut err),
+        );
+        assert_eq!(err.len, 0);
+
+        let floaty_wasm = wat::parse_str(
+            r#"(module
+                (func (result f32)
+                    f32.const 1.0)
+                (export "floaty" (func 0)))"#,
+        )
+        .unwrap();
+        save_wasm(cache_ptr, floaty_wasm.as_slice().into(), Some(&mut err));
+        assert_ne!(err.len, 0);
+
+        release_cache(cache_ptr);
+    }
+
+    #[test]
+    fn save_wasm_with_gas_metering_instantiates_and_runs() {
+        let dir: String = TempDir::new().unwrap().path().to_str().unwrap().to_owned();
+        let mut options = mock_cache_options(dir);
+        options.gas_metering = Some(GasMeteringConfig {
+            enabled: true,
+            cost_table: CostTable::default(),
+        });
+        let mut cache = mock_cache(options);
+
+        // the checksum is of the *original* module, even though the stored bytes
+        // are now the gas-instrumented version
+        let checksum = cache.save_wasm(CONTRACT).unwrap();
+
+        let mut instance = cache
+            .get_instance(&checksum, mock_backend(&[]), mock_instance_options())
+            .unwrap();
+
+        let info = mock_info("creator", &[]);
+        let msg = br#"{"verifier": "verifies", "beneficiary": "benefits"}"#;
+        let result = cosmwasm_vm::call_instantiate::<_, _, _, cosmwasm_std::Empty>(
+            &mut instance,
+            &mock_env(),
+            &info,
+            msg,
+        )
+        .unwrap();
+        assert!(result.into_result().is_ok());
+    }
+
+    #[test]
+    fn save_wasm_with_custom_cost_table_increases_gas_used() {
+        let info = mock_info("creator", &[]);
+        let msg = br#"{"verifier": "verifies", "beneficiary": "benefits"}"#;
+
+        let gas_used_with = |cost_table: CostTable| -> u64 {
+            let dir: String = TempDir::new().unwrap().path().to_str().unwrap().to_owned();
+            let mut options = mock_cache_options(dir);
+            options.gas_metering = Some(GasMeteringConfig {
+                enabled: true,
+                cost_table,
+            });
+            let mut cache = mock_cache(options);
+            let checksum = cache.save_wasm(CONTRACT).unwrap();
+            let mut instance = cache
+                .get_instance(&checksum, mock_backend(&[]), mock_instance_options())
+                .unwrap();
+            cosmwasm_vm::call_instantiate::<_, _, _, cosmwasm_std::Empty>(
+                &mut instance,
+                &mock_env(),
+                &info,
+                msg,
+            )
+            .unwrap()
+            .into_result()
+            .unwrap();
+            instance.create_gas_report().used_internally
+        };
+
+        let default_gas_used = gas_used_with(CostTable::default());
+
+        let mut boosted_cost_table = CostTable::default();
+        boosted_cost_table.default_cost *= 1000;
+        boosted_cost_table.memory_grow_cost *= 1000;
+        boosted_cost_table.call_cost *= 1000;
+        boosted_cost_table.branch_cost *= 1000;
+        let boosted_gas_used = gas_used_with(boosted_cost_table);
+
+        // a cost table with every opcode cost inflated a thousandfold must burn
+        // far more gas for the same contract call than the default table -- this
+        // is what would break if the configured cost table never reached the
+        // instrumentation pass and the module were always compiled with
+        // CostTable::default()
+        assert!(boosted_gas_used > default_gas_used);
+    }
 }